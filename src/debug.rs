@@ -0,0 +1,127 @@
+//! Interactive stepping debugger for `brainrust --debug <prog.bfk>`.
+//!
+//! Built entirely on [`Machine`]'s `step`-adjacent API (`current_position`,
+//! `data_pointer`, `tape_window`, breakpoints, `run_until`) — there's no
+//! separate execution path, so what you see here is exactly what `run`
+//! does under the hood.
+
+use std::io;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use brainrust::{
+    interpret::read_program,
+    io::{ByteRead, ByteWrite},
+    vm::vm::Machine,
+    Error,
+};
+
+pub fn run(path: &str) -> Result<(), Error> {
+    let program = read_program(path)?.compile();
+    let mut machine = Machine::<_, _, u128>::new(program, io::stdin(), io::stdout());
+    let mut finished = false;
+
+    let mut editor = DefaultEditor::new().expect("failed to start line editor");
+    println!("brainrust debugger -- {path}. Type `help` for commands.");
+
+    loop {
+        let line = match editor.readline("(bfdb) ") {
+            Ok(line) => line,
+            Err(ReadlineError::Eof) | Err(ReadlineError::Interrupted) => break,
+            Err(e) => {
+                eprintln!("readline error: {e}");
+                break;
+            }
+        };
+        let _ = editor.add_history_entry(line.as_str());
+
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("step") | Some("s") => {
+                if finished {
+                    println!("program already finished");
+                    continue;
+                }
+                let steps = words.next().and_then(|n| n.parse().ok()).unwrap_or(1);
+                finished = resume(&mut machine, steps)?;
+                print_status(&machine, finished);
+            }
+            Some("continue") | Some("c") => {
+                if finished {
+                    println!("program already finished");
+                    continue;
+                }
+                finished = resume(&mut machine, u64::MAX)?;
+                print_status(&machine, finished);
+            }
+            Some("break") | Some("b") => match words.next().and_then(|n| n.parse().ok()) {
+                Some(ip) => {
+                    machine.set_breakpoint(ip);
+                    println!("breakpoint set at {ip}");
+                }
+                None => println!("usage: break <instruction index>"),
+            },
+            Some("clear") => match words.next().and_then(|n| n.parse().ok()) {
+                Some(ip) => {
+                    if machine.clear_breakpoint(ip) {
+                        println!("breakpoint cleared at {ip}");
+                    } else {
+                        println!("no breakpoint at {ip}");
+                    }
+                }
+                None => println!("usage: clear <instruction index>"),
+            },
+            Some("tape") | Some("t") => {
+                let radius = words.next().and_then(|n| n.parse().ok()).unwrap_or(8);
+                println!("{:?}", machine.tape_window(radius));
+            }
+            Some("info") | Some("i") => print_status(&machine, finished),
+            Some("help") | Some("h") => print_help(),
+            Some("quit") | Some("q") => break,
+            Some(other) => println!("unknown command `{other}` (try `help`)"),
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Always executes the first instruction, even if a breakpoint sits on it,
+// so `step`/`continue` can move off the breakpoint execution is paused at.
+fn resume<R: ByteRead, W: ByteWrite>(
+    machine: &mut Machine<R, W, u128>,
+    max_steps: u64,
+) -> Result<bool, Error> {
+    if max_steps == 0 {
+        return Ok(false);
+    }
+    if machine.step()? {
+        return Ok(true);
+    }
+    machine.run_until(max_steps - 1)
+}
+
+fn print_status<R: ByteRead, W: ByteWrite>(machine: &Machine<R, W, u128>, finished: bool) {
+    if finished {
+        println!("program finished");
+        return;
+    }
+    println!(
+        "ip={} dp={} op={:?}",
+        machine.current_position(),
+        machine.data_pointer(),
+        machine.current_op(),
+    );
+}
+
+fn print_help() {
+    println!("commands:");
+    println!("  step [n]      execute up to n instructions (default 1), stopping at a breakpoint");
+    println!("  continue      run until a breakpoint or the program finishes");
+    println!("  break <ip>    set a breakpoint at instruction index ip");
+    println!("  clear <ip>    remove the breakpoint at ip");
+    println!("  tape [radius] print cells within radius of the data pointer (default 8)");
+    println!("  info          print the current instruction pointer, data pointer, and opcode");
+    println!("  quit          exit the debugger");
+}
@@ -1,15 +1,33 @@
-use std::io;
+use crate::{io::IoError, opt::BytecodeError, vm::vm};
 
 #[derive(Debug)]
 pub enum Error {
-    ProgramComplete,
-    UnmatchedOpenBracket(usize),
-    UnmatchedCloseBracket(usize),
-    Io(io::Error),
+    Language(vm::Error),
+    Io(IoError),
+    Bytecode(BytecodeError),
 }
 
-impl From<io::Error> for Error {
-    fn from(e: io::Error) -> Self {
+impl From<vm::Error> for Error {
+    fn from(e: vm::Error) -> Self {
+        Error::Language(e)
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(e: IoError) -> Self {
         Error::Io(e)
     }
 }
+
+impl From<BytecodeError> for Error {
+    fn from(e: BytecodeError) -> Self {
+        Error::Bytecode(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e.into())
+    }
+}
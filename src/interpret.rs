@@ -1,4 +1,7 @@
-use std::{convert::From, fs, io};
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use std::{fs, io};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Instruction {
@@ -38,7 +41,7 @@ impl Program {
         self.0.get(i).copied()
     }
 
-    pub fn iter(&self) -> std::slice::Iter<Instruction> {
+    pub fn iter(&self) -> core::slice::Iter<Instruction> {
         self.0.iter()
     }
 }
@@ -49,6 +52,16 @@ impl From<&str> for Program {
     }
 }
 
+impl Program {
+    /// Lowers this program into the optimized opcode stream `Machine`
+    /// actually runs. See [`crate::opt`] for the passes involved.
+    pub fn compile(&self) -> crate::opt::CompiledProgram {
+        crate::opt::CompiledProgram::from(self)
+    }
+}
+
+/// Reads a program from a file. Requires the `std` feature.
+#[cfg(feature = "std")]
 pub fn read_program(path: &str) -> io::Result<Program> {
     Ok(fs::read_to_string(path)?.as_str().into())
 }
@@ -56,10 +69,17 @@ pub fn read_program(path: &str) -> io::Result<Program> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+    use alloc::vec;
+
     #[test]
     fn str_to_program() {
         use Instruction::*;
-        assert_eq!(Program(vec![Read, Increment, While, Decrement, Write, Read, Increment, WhileEnd, MoveLeft, MoveRight]), ",+[-.,+]<>".into());
+        assert_eq!(
+            Program(vec![
+                Read, Increment, While, Decrement, Write, Read, Increment, WhileEnd, MoveLeft,
+                MoveRight
+            ]),
+            ",+[-.,+]<>".into()
+        );
     }
 }
@@ -0,0 +1,49 @@
+//! Minimal byte-oriented I/O traits.
+//!
+//! `std::io::{Read, Write}` aren't available without `std`, so `Machine` is
+//! bounded against these instead: one method each, read up to one byte /
+//! write a whole buffer, returning a crate-local [`IoError`]. When the
+//! `std` feature is on, blanket impls cover every `std::io::Read`/`Write`
+//! so existing callers (stdin, files, `Vec<u8>`, ...) keep working unchanged.
+
+#[cfg(feature = "std")]
+use std::io;
+
+/// Opaque I/O failure. Underlying `std::io::Error` detail isn't preserved,
+/// since no_std targets have nowhere to put it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoError;
+
+#[cfg(feature = "std")]
+impl From<io::Error> for IoError {
+    fn from(_: io::Error) -> Self {
+        IoError
+    }
+}
+
+pub trait ByteRead {
+    /// Reads the next byte, or `None` at end of input.
+    fn read_byte(&mut self) -> Result<Option<u8>, IoError>;
+}
+
+pub trait ByteWrite {
+    /// Writes the entire buffer.
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), IoError>;
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read> ByteRead for R {
+    fn read_byte(&mut self) -> Result<Option<u8>, IoError> {
+        let mut buf = [0_u8; 1];
+        let bytes_read = self.read(&mut buf)?;
+        Ok(if bytes_read > 0 { Some(buf[0]) } else { None })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> ByteWrite for W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), IoError> {
+        io::Write::write_all(self, buf)?;
+        Ok(())
+    }
+}
@@ -1,16 +1,28 @@
-use std::io;
+use std::{env, io};
 
-mod interpret;
-mod vm;
+use brainrust::{interpret::read_program, vm::vm::Machine, Error};
 
-use interpret::read_program;
-use vm::{vm::Machine, Error};
+mod debug;
 
 fn main() -> Result<(), Error> {
-    let program_name = "examples/cat.bfk";
+    let mut args = env::args().skip(1);
 
-    let machine =
-        Machine::<_, _, u128>::new(read_program(program_name)?, io::stdin(), io::stdout());
+    match args.next().as_deref() {
+        Some("--debug") => {
+            let path = args.next().unwrap_or_else(|| "examples/cat.bfk".to_string());
+            debug::run(&path)
+        }
+        Some(path) => run(path),
+        None => run("examples/cat.bfk"),
+    }
+}
+
+fn run(program_name: &str) -> Result<(), Error> {
+    let machine = Machine::<_, _, u128>::new(
+        read_program(program_name)?.compile(),
+        io::stdin(),
+        io::stdout(),
+    );
     machine.run()?;
     Ok(())
 }
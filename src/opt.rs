@@ -0,0 +1,542 @@
+//! Lowers a parsed [`Program`] into a compact opcode stream before
+//! execution, so tight loops like `[-]` or `[->++<]` don't pay for
+//! character-by-character dispatch.
+//!
+//! Three passes, run in order:
+//! 1. run-length encode consecutive `+`/`-` into [`Op::Add`] and `>`/`<`
+//!    into [`Op::Move`] (net signed count), dropping comments.
+//! 2. recognize clear loops (`[` `]` whose body is exactly `Add(±1)`) as
+//!    [`Op::SetZero`].
+//! 3. recognize balanced multiply/copy loops (body of only `Add`/`Move`,
+//!    pointer returns home, home cell's net delta is exactly `-1`) as a run
+//!    of [`Op::MulAdd`] followed by [`Op::SetZero`].
+//!
+//! Jump targets for the remaining `While`/`WhileEnd` pairs are then rebuilt
+//! over the reduced stream into a single `Vec<usize>` indexed by opcode
+//! position, replacing the two `HashMap`s the naive interpreter used.
+//!
+//! [`CompiledProgram::to_bytecode`]/[`CompiledProgram::from_bytecode`] give
+//! the result a compact on-disk form, so a `.bfc` artifact can be compiled
+//! once and reloaded without re-parsing source.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::interpret::{Instruction, Program};
+
+/// Sentinel stored in [`CompiledProgram`]'s jump table for a bracket with
+/// no match; `Machine::step` turns this into an `UnmatchedOpenBracket` /
+/// `UnmatchedCloseBracket` error if it's ever actually jumped to.
+pub const NO_TARGET: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add(i32),
+    Move(i32),
+    Write,
+    Read,
+    While,
+    WhileEnd,
+    SetZero,
+    MulAdd { offset: i32, factor: i32 },
+}
+
+/// An optimized opcode stream ready for [`crate::vm::vm::Machine`] to run.
+#[derive(Debug, PartialEq)]
+pub struct CompiledProgram {
+    ops: Vec<Op>,
+    jump_targets: Vec<usize>,
+}
+
+impl CompiledProgram {
+    pub fn get(&self, i: usize) -> Option<Op> {
+        self.ops.get(i).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// The matching bracket for the `While`/`WhileEnd` opcode at `i`, or
+    /// [`NO_TARGET`] if it has none.
+    pub fn jump_target(&self, i: usize) -> usize {
+        self.jump_targets[i]
+    }
+
+    /// Encodes this opcode stream as a portable `.bfc` artifact: a magic +
+    /// version header, then one byte-tag-plus-varint-operand per opcode.
+    /// `While`/`WhileEnd` carry their absolute jump target instead of an
+    /// operand, so the jump table doesn't need to be shipped separately.
+    pub fn to_bytecode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&MAGIC);
+        out.push(VERSION);
+
+        for (i, op) in self.ops.iter().enumerate() {
+            match op {
+                Op::Add(delta) => {
+                    out.push(TAG_ADD);
+                    write_svarint(&mut out, i64::from(*delta));
+                }
+                Op::Move(delta) => {
+                    out.push(TAG_MOVE);
+                    write_svarint(&mut out, i64::from(*delta));
+                }
+                Op::Write => out.push(TAG_WRITE),
+                Op::Read => out.push(TAG_READ),
+                Op::While => {
+                    out.push(TAG_WHILE);
+                    write_uvarint(&mut out, self.jump_targets[i] as u64);
+                }
+                Op::WhileEnd => {
+                    out.push(TAG_WHILE_END);
+                    write_uvarint(&mut out, self.jump_targets[i] as u64);
+                }
+                Op::SetZero => out.push(TAG_SET_ZERO),
+                Op::MulAdd { offset, factor } => {
+                    out.push(TAG_MUL_ADD);
+                    write_svarint(&mut out, i64::from(*offset));
+                    write_svarint(&mut out, i64::from(*factor));
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Decodes a `.bfc` artifact produced by [`Self::to_bytecode`].
+    pub fn from_bytecode(bytes: &[u8]) -> Result<Self, BytecodeError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(BytecodeError::Truncated);
+        }
+        if bytes[..MAGIC.len()] != MAGIC {
+            return Err(BytecodeError::BadMagic);
+        }
+        if bytes[MAGIC.len()] != VERSION {
+            return Err(BytecodeError::UnsupportedVersion(bytes[MAGIC.len()]));
+        }
+
+        let mut pos = HEADER_LEN;
+        let mut ops = Vec::new();
+        let mut jump_targets = Vec::new();
+
+        while pos < bytes.len() {
+            let tag = bytes[pos];
+            pos += 1;
+
+            let (op, target) = match tag {
+                TAG_ADD => {
+                    let (v, n) = read_svarint_i32(bytes, pos)?;
+                    pos += n;
+                    (Op::Add(v), NO_TARGET)
+                }
+                TAG_MOVE => {
+                    let (v, n) = read_svarint_i32(bytes, pos)?;
+                    pos += n;
+                    (Op::Move(v), NO_TARGET)
+                }
+                TAG_WRITE => (Op::Write, NO_TARGET),
+                TAG_READ => (Op::Read, NO_TARGET),
+                TAG_WHILE => {
+                    let (v, n) = read_uvarint(bytes, pos)?;
+                    pos += n;
+                    (Op::While, v as usize)
+                }
+                TAG_WHILE_END => {
+                    let (v, n) = read_uvarint(bytes, pos)?;
+                    pos += n;
+                    (Op::WhileEnd, v as usize)
+                }
+                TAG_SET_ZERO => (Op::SetZero, NO_TARGET),
+                TAG_MUL_ADD => {
+                    let (offset, n) = read_svarint_i32(bytes, pos)?;
+                    pos += n;
+                    let (factor, n) = read_svarint_i32(bytes, pos)?;
+                    pos += n;
+                    (Op::MulAdd { offset, factor }, NO_TARGET)
+                }
+                unknown => return Err(BytecodeError::UnknownOpcode(unknown)),
+            };
+
+            ops.push(op);
+            jump_targets.push(target);
+        }
+
+        for (i, target) in jump_targets.iter().enumerate() {
+            if *target != NO_TARGET && *target >= ops.len() {
+                return Err(BytecodeError::JumpOutOfRange(i));
+            }
+        }
+
+        Ok(Self { ops, jump_targets })
+    }
+}
+
+const MAGIC: [u8; 3] = *b"BFC";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 1;
+
+const TAG_ADD: u8 = 0;
+const TAG_MOVE: u8 = 1;
+const TAG_WRITE: u8 = 2;
+const TAG_READ: u8 = 3;
+const TAG_WHILE: u8 = 4;
+const TAG_WHILE_END: u8 = 5;
+const TAG_SET_ZERO: u8 = 6;
+const TAG_MUL_ADD: u8 = 7;
+
+/// Error decoding a `.bfc` bytecode artifact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BytecodeError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnknownOpcode(u8),
+    Truncated,
+    JumpOutOfRange(usize),
+    OperandOutOfRange(i64),
+}
+
+fn write_uvarint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let mut byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+fn write_svarint(out: &mut Vec<u8>, value: i64) {
+    let zigzag = ((value << 1) ^ (value >> 63)) as u64;
+    write_uvarint(out, zigzag);
+}
+
+fn read_uvarint(bytes: &[u8], pos: usize) -> Result<(u64, usize), BytecodeError> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    let mut consumed = 0;
+
+    loop {
+        let byte = *bytes.get(pos + consumed).ok_or(BytecodeError::Truncated)?;
+        consumed += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+
+    Ok((value, consumed))
+}
+
+fn read_svarint(bytes: &[u8], pos: usize) -> Result<(i64, usize), BytecodeError> {
+    let (zigzag, consumed) = read_uvarint(bytes, pos)?;
+    let value = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+    Ok((value, consumed))
+}
+
+/// Reads a signed varint and range-checks it against `i32`, so a crafted
+/// artifact can't smuggle in an operand (e.g. `i32::MAX + 1`) that doesn't
+/// fit an opcode's `i32` field.
+fn read_svarint_i32(bytes: &[u8], pos: usize) -> Result<(i32, usize), BytecodeError> {
+    let (value, consumed) = read_svarint(bytes, pos)?;
+    i32::try_from(value)
+        .map(|v| (v, consumed))
+        .map_err(|_| BytecodeError::OperandOutOfRange(value))
+}
+
+impl From<&Program> for CompiledProgram {
+    fn from(program: &Program) -> Self {
+        let ops = fuse_run_lengths(program);
+        let ops = recognize_clear_loops(ops);
+        let ops = recognize_multiply_loops(ops);
+        let jump_targets = link_jumps(&ops);
+        Self { ops, jump_targets }
+    }
+}
+
+impl From<Program> for CompiledProgram {
+    fn from(program: Program) -> Self {
+        Self::from(&program)
+    }
+}
+
+fn fuse_run_lengths(program: &Program) -> Vec<Op> {
+    let mut ops = Vec::new();
+    let mut i = 0;
+
+    while let Some(instruction) = program.get(i) {
+        match instruction {
+            Instruction::Increment | Instruction::Decrement => {
+                let mut delta: i32 = 0;
+                while let Some(next) = program.get(i) {
+                    match next {
+                        Instruction::Increment => delta += 1,
+                        Instruction::Decrement => delta -= 1,
+                        _ => break,
+                    }
+                    i += 1;
+                }
+                ops.push(Op::Add(delta));
+            }
+            Instruction::MoveRight | Instruction::MoveLeft => {
+                let mut delta: i32 = 0;
+                while let Some(next) = program.get(i) {
+                    match next {
+                        Instruction::MoveRight => delta += 1,
+                        Instruction::MoveLeft => delta -= 1,
+                        _ => break,
+                    }
+                    i += 1;
+                }
+                ops.push(Op::Move(delta));
+            }
+            Instruction::Write => {
+                ops.push(Op::Write);
+                i += 1;
+            }
+            Instruction::Read => {
+                ops.push(Op::Read);
+                i += 1;
+            }
+            Instruction::While => {
+                ops.push(Op::While);
+                i += 1;
+            }
+            Instruction::WhileEnd => {
+                ops.push(Op::WhileEnd);
+                i += 1;
+            }
+            Instruction::Comment(_) => {
+                i += 1;
+            }
+        }
+    }
+
+    ops
+}
+
+fn recognize_clear_loops(ops: Vec<Op>) -> Vec<Op> {
+    let mut out = Vec::with_capacity(ops.len());
+    let mut i = 0;
+
+    while i < ops.len() {
+        if let (Some(Op::While), Some(Op::Add(delta)), Some(Op::WhileEnd)) =
+            (ops.get(i), ops.get(i + 1), ops.get(i + 2))
+        {
+            if *delta == 1 || *delta == -1 {
+                out.push(Op::SetZero);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(ops[i]);
+        i += 1;
+    }
+
+    out
+}
+
+fn recognize_multiply_loops(ops: Vec<Op>) -> Vec<Op> {
+    let mut out = Vec::with_capacity(ops.len());
+    let mut i = 0;
+
+    while i < ops.len() {
+        if ops[i] == Op::While {
+            if let Some(end) = matching_while_end(&ops, i) {
+                if let Some(deltas) = multiply_loop_deltas(&ops[i + 1..end]) {
+                    out.extend(
+                        deltas
+                            .into_iter()
+                            .map(|(offset, factor)| Op::MulAdd { offset, factor }),
+                    );
+                    out.push(Op::SetZero);
+                    i = end + 1;
+                    continue;
+                }
+            }
+        }
+        out.push(ops[i]);
+        i += 1;
+    }
+
+    out
+}
+
+fn matching_while_end(ops: &[Op], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (j, op) in ops.iter().enumerate().skip(open) {
+        match op {
+            Op::While => depth += 1,
+            Op::WhileEnd => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(j);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// If `body` is only `Add`/`Move`, the pointer ends up back home, and the
+/// home cell's net delta is exactly `-1`, returns the nonzero `(offset,
+/// factor)` pairs for every other cell it touches.
+fn multiply_loop_deltas(body: &[Op]) -> Option<Vec<(i32, i32)>> {
+    let mut offset: i32 = 0;
+    let mut deltas: BTreeMap<i32, i32> = BTreeMap::new();
+
+    for op in body {
+        match op {
+            Op::Add(delta) => *deltas.entry(offset).or_insert(0) += delta,
+            Op::Move(delta) => offset += delta,
+            _ => return None,
+        }
+    }
+
+    if offset != 0 || deltas.get(&0).copied().unwrap_or(0) != -1 {
+        return None;
+    }
+
+    Some(
+        deltas
+            .into_iter()
+            .filter(|(cell_offset, factor)| *cell_offset != 0 && *factor != 0)
+            .collect(),
+    )
+}
+
+fn link_jumps(ops: &[Op]) -> Vec<usize> {
+    let mut jump_targets = alloc::vec![NO_TARGET; ops.len()];
+    let mut open_stack = Vec::new();
+
+    for (i, op) in ops.iter().enumerate() {
+        match op {
+            Op::While => open_stack.push(i),
+            Op::WhileEnd => {
+                if let Some(open) = open_stack.pop() {
+                    jump_targets[open] = i;
+                    jump_targets[i] = open;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    jump_targets
+}
+
+/// Dumps a compiled opcode stream in human-readable form, e.g.
+/// `0003  ADD +4`, `0012  MULADD off=+1 factor=3`, `0015  JZ -> 0030`.
+/// Gated behind the `disasm` feature so `no_std` builds that don't need it
+/// skip the extra `alloc`-heavy string formatting.
+#[cfg(feature = "disasm")]
+impl CompiledProgram {
+    pub fn disassemble(&self) -> alloc::string::String {
+        use core::fmt::Write;
+
+        let mut out = alloc::string::String::new();
+
+        for (i, op) in self.ops.iter().enumerate() {
+            let _ = match op {
+                Op::Add(delta) => writeln!(out, "{:04}  ADD {:+}", i, delta),
+                Op::Move(delta) => writeln!(out, "{:04}  MOVE {:+}", i, delta),
+                Op::Write => writeln!(out, "{:04}  WRITE", i),
+                Op::Read => writeln!(out, "{:04}  READ", i),
+                Op::SetZero => writeln!(out, "{:04}  SETZERO", i),
+                Op::MulAdd { offset, factor } => {
+                    writeln!(out, "{:04}  MULADD off={:+} factor={}", i, offset, factor)
+                }
+                Op::While => writeln!(out, "{:04}  JZ -> {:04}", i, self.jump_targets[i]),
+                Op::WhileEnd => writeln!(out, "{:04}  JNZ -> {:04}", i, self.jump_targets[i]),
+            };
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_clear_loop() {
+        let program: Program = "+++[-]".into();
+        let compiled = program.compile();
+        assert_eq!(compiled.get(0), Some(Op::Add(3)));
+        assert_eq!(compiled.get(1), Some(Op::SetZero));
+        assert_eq!(compiled.len(), 2);
+    }
+
+    #[test]
+    fn recognizes_multiply_loop() {
+        let program: Program = "[->++<]".into();
+        let compiled = program.compile();
+        assert_eq!(compiled.get(0), Some(Op::MulAdd { offset: 1, factor: 2 }));
+        assert_eq!(compiled.get(1), Some(Op::SetZero));
+        assert_eq!(compiled.len(), 2);
+    }
+
+    #[test]
+    fn bytecode_roundtrip() {
+        let program: Program = "+++[-],.[->++<]".into();
+        let compiled = program.compile();
+        let bytes = compiled.to_bytecode();
+        let decoded = CompiledProgram::from_bytecode(&bytes).unwrap();
+        assert_eq!(compiled, decoded);
+    }
+
+    #[test]
+    fn from_bytecode_rejects_oversized_operand() {
+        let mut bytes = alloc::vec![b'B', b'F', b'C', 1, TAG_ADD];
+        write_svarint(&mut bytes, i64::from(i32::MAX) + 1);
+        assert_eq!(
+            CompiledProgram::from_bytecode(&bytes),
+            Err(BytecodeError::OperandOutOfRange(i64::from(i32::MAX) + 1))
+        );
+    }
+
+    #[test]
+    fn from_bytecode_rejects_bad_magic() {
+        let bytes = [0, 0, 0, 1];
+        assert_eq!(
+            CompiledProgram::from_bytecode(&bytes),
+            Err(BytecodeError::BadMagic)
+        );
+    }
+
+    #[test]
+    fn from_bytecode_rejects_unknown_opcode() {
+        let mut bytes = alloc::vec![b'B', b'F', b'C', 1];
+        bytes.push(99);
+        assert_eq!(
+            CompiledProgram::from_bytecode(&bytes),
+            Err(BytecodeError::UnknownOpcode(99))
+        );
+    }
+
+    #[cfg(feature = "disasm")]
+    #[test]
+    fn disassembles_opcodes() {
+        // Trailing `[>]` is a pointer scan, not a clear/multiply loop, so it
+        // survives the optimizer as a real `While`/`WhileEnd` pair.
+        let program: Program = "+++[-],[->++<]>[>]".into();
+        let dump = program.compile().disassemble();
+        assert!(dump.contains("ADD +3"));
+        assert!(dump.contains("SETZERO"));
+        assert!(dump.contains("READ"));
+        assert!(dump.contains("MULADD off=+1 factor=2"));
+        assert!(dump.contains("JZ ->"));
+        assert!(dump.contains("JNZ ->"));
+    }
+}
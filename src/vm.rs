@@ -1,36 +1,24 @@
-#[derive(Debug)]
-pub enum Error {
-    Language(vm::Error),
-    Io(std::io::Error),
-}
-
-impl From<vm::Error> for Error {
-    fn from(e: vm::Error) -> Self {
-        Error::Language(e)
-    }
-}
-
-impl From<std::io::Error> for Error {
-    fn from(e: std::io::Error) -> Self {
-        Error::Io(e)
-    }
-}
+//! The Brainfuck virtual machine: a tape of memory cells driven by a
+//! [`CompiledProgram`](crate::opt::CompiledProgram)'s opcode stream.
 
 pub mod vm {
     use num_traits::{
         cast::FromPrimitive,
         identities::{one, zero},
         int::PrimInt,
-        ops::wrapping::{WrappingAdd, WrappingSub},
+        ops::wrapping::{WrappingAdd, WrappingMul, WrappingSub},
         sign::Unsigned,
     };
 
-    use std::{
-        collections::{HashMap, VecDeque},
-        io::{Read, Write},
+    use alloc::{
+        collections::{BTreeSet, VecDeque},
+        vec::Vec,
     };
 
-    use crate::interpret::{Instruction, Program};
+    use crate::{
+        io::{ByteRead, ByteWrite},
+        opt::{CompiledProgram, Op, NO_TARGET},
+    };
 
     #[derive(Debug)]
     pub enum Error {
@@ -40,87 +28,191 @@ pub mod vm {
 
     #[derive(Debug)]
     pub struct Machine<R, W, I> {
-        program: Program,
+        program: CompiledProgram,
         instruction_ptr: usize,
         memory: VecDeque<I>,
         data_ptr: usize,
-        open_to_close: HashMap<usize, usize>,
-        close_to_open: HashMap<usize, usize>,
+        breakpoints: BTreeSet<usize>,
         input: R,
         output: W,
     }
 
-    impl<R: Read, W: Write, I: PrimInt + WrappingAdd + WrappingSub + FromPrimitive + Unsigned>
-        Machine<R, W, I>
+    impl<
+            R: ByteRead,
+            W: ByteWrite,
+            I: PrimInt + WrappingAdd + WrappingSub + WrappingMul + FromPrimitive + Unsigned,
+        > Machine<R, W, I>
     {
-        pub fn new(program: Program, input: R, output: W) -> Self {
-            use Instruction::*;
-
-            let mut open_to_close = HashMap::new();
-            let mut close_to_open = HashMap::new();
-            let mut open_stack = Vec::new();
-
-            for (i, instruction) in program.iter().copied().enumerate() {
-                if instruction == While {
-                    open_stack.push(i);
-                }
-                if instruction == WhileEnd {
-                    if let Some(open_loc) = open_stack.pop() {
-                        open_to_close.insert(open_loc, i);
-                        close_to_open.insert(i, open_loc);
-                    }
-                }
-            }
-
-            open_to_close.shrink_to_fit();
-            close_to_open.shrink_to_fit();
-
+        pub fn new(program: CompiledProgram, input: R, output: W) -> Self {
             Self {
                 program,
                 instruction_ptr: 0,
-                memory: VecDeque::from(vec![zero()]),
+                memory: VecDeque::from(alloc::vec![zero()]),
                 data_ptr: 0,
-                open_to_close,
-                close_to_open,
+                breakpoints: BTreeSet::new(),
                 input,
                 output,
             }
         }
 
-        // Returns true if the machine is finished.
-        pub fn step(&mut self) -> Result<bool, super::Error> {
-            use Instruction::*;
+        /// The index of the next opcode to execute.
+        pub fn current_position(&self) -> usize {
+            self.instruction_ptr
+        }
 
-            let instruction = self.program.get(self.instruction_ptr);
+        /// The opcode at [`Self::current_position`], or `None` if the
+        /// program has finished.
+        pub fn current_op(&self) -> Option<Op> {
+            self.program.get(self.instruction_ptr)
+        }
 
-            if instruction.is_none() {
-                return Ok(true);
+        /// The tape index the data pointer is currently on.
+        pub fn data_pointer(&self) -> usize {
+            self.data_ptr
+        }
+
+        /// The allocated cells within `radius` of the data pointer, in tape
+        /// order, starting at `data_pointer().saturating_sub(radius)`. Cells
+        /// the tape hasn't grown into yet are simply absent from either end.
+        pub fn tape_window(&self, radius: usize) -> Vec<I> {
+            let start = self.data_ptr.saturating_sub(radius);
+            let end = self
+                .data_ptr
+                .saturating_add(radius)
+                .saturating_add(1)
+                .min(self.memory.len());
+            self.memory
+                .iter()
+                .skip(start)
+                .take(end.saturating_sub(start))
+                .copied()
+                .collect()
+        }
+
+        /// Sets a breakpoint at opcode index `ip`.
+        pub fn set_breakpoint(&mut self, ip: usize) {
+            self.breakpoints.insert(ip);
+        }
+
+        /// Removes the breakpoint at opcode index `ip`, returning whether
+        /// one was set.
+        pub fn clear_breakpoint(&mut self, ip: usize) -> bool {
+            self.breakpoints.remove(&ip)
+        }
+
+        /// Whether a breakpoint is set at opcode index `ip`.
+        pub fn has_breakpoint(&self, ip: usize) -> bool {
+            self.breakpoints.contains(&ip)
+        }
+
+        /// All currently set breakpoints, in ascending order.
+        pub fn breakpoints(&self) -> impl Iterator<Item = &usize> {
+            self.breakpoints.iter()
+        }
+
+        /// Steps until the program finishes, a breakpoint is reached, or
+        /// `max_steps` steps have run, whichever comes first. Returns
+        /// `true` if the program is now finished. A breakpoint at the
+        /// current position is honored before that step runs, so setting
+        /// one where execution is already paused halts immediately.
+        pub fn run_until(&mut self, max_steps: u64) -> Result<bool, crate::Error> {
+            for _ in 0..max_steps {
+                if self.has_breakpoint(self.instruction_ptr) {
+                    return Ok(false);
+                }
+                if self.step()? {
+                    return Ok(true);
+                }
             }
-            let instruction = instruction.unwrap();
+            Ok(false)
+        }
 
-            match instruction {
-                MoveRight => {
-                    self.data_ptr += 1;
-                    if self.memory.get(self.data_ptr).is_none() {
-                        self.memory.push_back(zero());
-                    }
+        /// Resolves a cell `offset` away from the current `data_ptr`,
+        /// growing the tape as needed, without moving `data_ptr` itself.
+        fn offset_index(&mut self, offset: i32) -> usize {
+            if offset >= 0 {
+                let idx = self.data_ptr + offset as usize;
+                while idx >= self.memory.len() {
+                    self.memory.push_back(zero());
                 }
-                MoveLeft => {
-                    if self.data_ptr == 0 {
+                idx
+            } else {
+                let magnitude = offset.unsigned_abs() as usize;
+                if magnitude <= self.data_ptr {
+                    self.data_ptr - magnitude
+                } else {
+                    let shift = magnitude - self.data_ptr;
+                    for _ in 0..shift {
                         self.memory.push_front(zero());
-                    } else {
-                        self.data_ptr -= 1;
                     }
+                    self.data_ptr += shift;
+                    0
                 }
-                Increment => {
-                    let x = self.memory.get_mut(self.data_ptr).unwrap();
-                    *x = x.wrapping_add(&one());
+            }
+        }
+
+        // Builds up `magnitude` one bit at a time via wrapping doubling, so
+        // a fused `Add`/`MulAdd` whose magnitude exceeds `I::max_value()`
+        // (run-length fusion and multiply-loop folding can both produce
+        // such deltas) wraps the same way the unfused loop of individual
+        // `+1`s would, instead of panicking.
+        fn wrapping_from_u64(mut magnitude: u64) -> I {
+            let mut result = zero::<I>();
+            let mut place = one::<I>();
+            for _ in 0..64 {
+                if magnitude & 1 == 1 {
+                    result = result.wrapping_add(&place);
                 }
-                Decrement => {
+                place = place.wrapping_add(&place);
+                magnitude >>= 1;
+            }
+            result
+        }
+
+        // Widened to `i64` so negating `i32::MIN` (reachable via a crafted
+        // `.bfc` file) can't overflow.
+        fn signed(value: i32) -> I {
+            let value = i64::from(value);
+            let magnitude = Self::wrapping_from_u64(value.unsigned_abs());
+            if value >= 0 {
+                magnitude
+            } else {
+                zero::<I>().wrapping_sub(&magnitude)
+            }
+        }
+
+        // Returns true if the machine is finished.
+        pub fn step(&mut self) -> Result<bool, crate::Error> {
+            let op = match self.program.get(self.instruction_ptr) {
+                Some(op) => op,
+                None => return Ok(true),
+            };
+
+            match op {
+                Op::Add(delta) => {
                     let x = self.memory.get_mut(self.data_ptr).unwrap();
-                    *x = x.wrapping_sub(&one());
+                    *x = x.wrapping_add(&Self::signed(delta));
                 }
-                Write => {
+                Op::Move(delta) => {
+                    if delta >= 0 {
+                        self.data_ptr += delta as usize;
+                        while self.data_ptr >= self.memory.len() {
+                            self.memory.push_back(zero());
+                        }
+                    } else {
+                        let magnitude = delta.unsigned_abs() as usize;
+                        if magnitude <= self.data_ptr {
+                            self.data_ptr -= magnitude;
+                        } else {
+                            let shift = magnitude - self.data_ptr;
+                            for _ in 0..shift {
+                                self.memory.push_front(zero());
+                            }
+                            self.data_ptr = 0;
+                        }
+                    }
+                }
+                Op::Write => {
                     let buf = [match I::from_u16(256) {
                         Some(i) => *self.memory.get(self.data_ptr).unwrap() % i,
                         None => *self.memory.get(self.data_ptr).unwrap(),
@@ -129,39 +221,46 @@ pub mod vm {
                     .unwrap()];
                     self.output.write_all(&buf)?;
                 }
-                Read => {
-                    let mut buf = [0_u8; 1];
-                    let bytes_read = self.input.read(&mut buf)?;
-                    let input = if bytes_read > 0 {
-                        I::from_u8(buf[0]).unwrap()
-                    } else {
-                        I::max_value()
+                Op::Read => {
+                    let input = match self.input.read_byte()? {
+                        Some(byte) => I::from_u8(byte).unwrap(),
+                        None => I::max_value(),
                     };
                     *self.memory.get_mut(self.data_ptr).unwrap() = input;
                 }
-                While => {
+                Op::SetZero => {
+                    let x = self.memory.get_mut(self.data_ptr).unwrap();
+                    *x = zero();
+                }
+                Op::MulAdd { offset, factor } => {
+                    let home = *self.memory.get(self.data_ptr).unwrap();
+                    let factor = Self::signed(factor);
+                    let idx = self.offset_index(offset);
+                    let cell = self.memory.get_mut(idx).unwrap();
+                    *cell = cell.wrapping_add(&home.wrapping_mul(&factor));
+                }
+                Op::While => {
                     if *self.memory.get(self.data_ptr).unwrap() == zero() {
-                        match self.open_to_close.get(&self.instruction_ptr) {
-                            Some(close_loc) => self.instruction_ptr = *close_loc,
-                            None => {
+                        match self.program.jump_target(self.instruction_ptr) {
+                            NO_TARGET => {
                                 return Err(Error::UnmatchedOpenBracket(self.instruction_ptr).into())
                             }
+                            close_loc => self.instruction_ptr = close_loc,
                         }
                     }
                 }
-                WhileEnd => {
+                Op::WhileEnd => {
                     if *self.memory.get(self.data_ptr).unwrap() != zero() {
-                        match self.close_to_open.get(&self.instruction_ptr) {
-                            Some(open_loc) => self.instruction_ptr = *open_loc,
-                            None => {
+                        match self.program.jump_target(self.instruction_ptr) {
+                            NO_TARGET => {
                                 return Err(
                                     Error::UnmatchedCloseBracket(self.instruction_ptr).into()
                                 )
                             }
+                            open_loc => self.instruction_ptr = open_loc,
                         }
                     }
                 }
-                Comment(_) => {}
             };
 
             self.instruction_ptr += 1;
@@ -169,7 +268,7 @@ pub mod vm {
             Ok(false)
         }
 
-        pub fn run(mut self) -> Result<u64, super::Error> {
+        pub fn run(mut self) -> Result<u64, crate::Error> {
             let mut time = 0;
             loop {
                 time += 1;
@@ -187,13 +286,94 @@ pub mod vm {
 #[cfg(test)]
 mod tests {
     use super::vm::*;
+    use alloc::vec::Vec;
 
     #[test]
     fn cat() {
         let input: &[u8] = &[47, 0, 38, 1, 200];
-        let mut output = Vec::new();
-        let m = Machine::<_, _, u16>::new(",+[-.,+]".into(), input, &mut output);
+        let mut output: Vec<u8> = Vec::new();
+        let program: crate::interpret::Program = ",+[-.,+]".into();
+        let m = Machine::<_, _, u16>::new(program.compile(), input, &mut output);
         assert!(m.run().is_ok());
         assert_eq!(input, output.as_slice());
     }
+
+    #[test]
+    fn clear_loop() {
+        let input: &[u8] = &[];
+        let mut output: Vec<u8> = Vec::new();
+        let program: crate::interpret::Program = "+++++[-].".into();
+        let m = Machine::<_, _, u8>::new(program.compile(), input, &mut output);
+        assert!(m.run().is_ok());
+        assert_eq!(output, alloc::vec![0]);
+    }
+
+    #[test]
+    fn multiply_loop() {
+        // Sets cell 0 to 5, then doubles it into cell 1 via a multiply loop.
+        let input: &[u8] = &[];
+        let mut output: Vec<u8> = Vec::new();
+        let program: crate::interpret::Program = "+++++[->++<].>.".into();
+        let m = Machine::<_, _, u8>::new(program.compile(), input, &mut output);
+        assert!(m.run().is_ok());
+        assert_eq!(output, alloc::vec![0, 10]);
+    }
+
+    #[test]
+    fn breakpoints_pause_run_until() {
+        let input: &[u8] = &[];
+        let mut output: Vec<u8> = Vec::new();
+        let program: crate::interpret::Program = "+++>++".into();
+        let mut m = Machine::<_, _, u8>::new(program.compile(), input, &mut output);
+
+        m.set_breakpoint(1);
+        assert!(!m.run_until(10).unwrap());
+        assert_eq!(m.current_position(), 1);
+        assert_eq!(m.data_pointer(), 0);
+        assert_eq!(m.tape_window(1), alloc::vec![3]);
+
+        assert!(m.clear_breakpoint(1));
+        assert!(m.run_until(10).unwrap());
+        assert_eq!(m.data_pointer(), 1);
+        assert_eq!(m.tape_window(1), alloc::vec![3, 2]);
+    }
+
+    #[test]
+    fn steps_i32_min_add_without_overflow_panic() {
+        // header (`BFC`, version 1) + one `Add` opcode carrying a
+        // zigzag-encoded `i32::MIN` operand, as a maliciously crafted
+        // `.bfc` file might.
+        let bytes = [b'B', b'F', b'C', 1, 0, 0xff, 0xff, 0xff, 0xff, 0x0f];
+        let program = crate::opt::CompiledProgram::from_bytecode(&bytes).unwrap();
+
+        let input: &[u8] = &[];
+        let mut output: Vec<u8> = Vec::new();
+        let mut m = Machine::<_, _, u128>::new(program, input, &mut output);
+
+        assert!(m.step().is_ok());
+    }
+
+    #[test]
+    fn fused_add_wraps_past_cell_max() {
+        // Run-length fusion collapses this into a single `Add(300)`, whose
+        // magnitude exceeds `u8::MAX` — it should wrap the same way running
+        // 300 individual `+`s would, not panic.
+        let input: &[u8] = &[];
+        let mut output: Vec<u8> = Vec::new();
+        let source = alloc::format!("{}.", "+".repeat(300));
+        let program: crate::interpret::Program = source.as_str().into();
+        let m = Machine::<_, _, u8>::new(program.compile(), input, &mut output);
+        assert!(m.run().is_ok());
+        assert_eq!(output, alloc::vec![(300u32 % 256) as u8]);
+    }
+
+    #[test]
+    fn tape_window_does_not_overflow_on_large_radius() {
+        let input: &[u8] = &[];
+        let mut output: Vec<u8> = Vec::new();
+        let program: crate::interpret::Program = "+".into();
+        let mut m = Machine::<_, _, u8>::new(program.compile(), input, &mut output);
+        assert!(m.step().is_ok());
+        assert_eq!(m.tape_window(usize::MAX), alloc::vec![1]);
+    }
 }